@@ -0,0 +1,411 @@
+// A tiny expression language used to describe substitution rules in `rules.yaml`.
+// Supports boolean logic over A/B/C and arithmetic over D/E/F, e.g.
+// `A && B && !C` or `D + D * (E - F) / 25.5`.
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Num(f64),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DslError {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    UnexpectedToken(String),
+    TrailingTokens,
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedChar(c) => write!(f, "unexpected character '{c}'"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            Self::UnexpectedToken(t) => write!(f, "unexpected token '{t}'"),
+            Self::TrailingTokens => write!(f, "trailing tokens after expression"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    And,
+    Or,
+    Not,
+    EqEq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, DslError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' | '\r' => i += 1,
+            '+' => {
+                tokens.push(Token::Plus);
+                i += 1;
+            }
+            '-' => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            '*' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            '/' => {
+                tokens.push(Token::Slash);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '&' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '!' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::NotEq);
+                i += 2;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '=' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::EqEq);
+                i += 2;
+            }
+            '<' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::Le);
+                i += 2;
+            }
+            '<' => {
+                tokens.push(Token::Lt);
+                i += 1;
+            }
+            '>' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::Ge);
+                i += 2;
+            }
+            '>' => {
+                tokens.push(Token::Gt);
+                i += 1;
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text
+                    .parse()
+                    .map_err(|_| DslError::UnexpectedToken(text.clone()))?;
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            c => return Err(DslError::UnexpectedChar(c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Eq,
+    Neq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    Var(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+}
+
+// Precedence-climbing (Pratt) parser: or < and < equality < comparison < additive < multiplicative < unary < primary.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, DslError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_and()?;
+        while matches!(self.peek(), Some(Token::Or)) {
+            self.next();
+            let rhs = self.parse_and()?;
+            lhs = Expr::Binary(Box::new(lhs), BinOp::Or, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_equality()?;
+        while matches!(self.peek(), Some(Token::And)) {
+            self.next();
+            let rhs = self.parse_equality()?;
+            lhs = Expr::Binary(Box::new(lhs), BinOp::And, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_equality(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_comparison()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::EqEq) => BinOp::Eq,
+                Some(Token::NotEq) => BinOp::Neq,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_additive()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Lt) => BinOp::Lt,
+                Some(Token::Le) => BinOp::Le,
+                Some(Token::Gt) => BinOp::Gt,
+                Some(Token::Ge) => BinOp::Ge,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_additive()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinOp::Add,
+                Some(Token::Minus) => BinOp::Sub,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, DslError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinOp::Mul,
+                Some(Token::Slash) => BinOp::Div,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, DslError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Minus) => {
+                self.next();
+                Ok(Expr::Neg(Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, DslError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Num(n)),
+            Some(Token::Ident(name)) => Ok(Expr::Var(name)),
+            Some(Token::LParen) => {
+                let expr = self.parse_expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    Some(other) => Err(DslError::UnexpectedToken(format!("{other:?}"))),
+                    None => Err(DslError::UnexpectedEnd),
+                }
+            }
+            Some(other) => Err(DslError::UnexpectedToken(format!("{other:?}"))),
+            None => Err(DslError::UnexpectedEnd),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, DslError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(DslError::TrailingTokens);
+    }
+    Ok(expr)
+}
+
+// Evaluates a parsed expression against a variable map. Returns `None` on type
+// mismatches (e.g. `&&` over numbers) or unknown variables.
+pub fn eval(expr: &Expr, vars: &HashMap<String, Value>) -> Option<Value> {
+    match expr {
+        Expr::Num(n) => Some(Value::Num(*n)),
+        Expr::Var(name) => vars.get(name).copied(),
+        Expr::Not(inner) => match eval(inner, vars)? {
+            Value::Bool(b) => Some(Value::Bool(!b)),
+            Value::Num(_) => None,
+        },
+        Expr::Neg(inner) => match eval(inner, vars)? {
+            Value::Num(n) => Some(Value::Num(-n)),
+            Value::Bool(_) => None,
+        },
+        Expr::Binary(lhs, op, rhs) => {
+            let lhs = eval(lhs, vars)?;
+            let rhs = eval(rhs, vars)?;
+            eval_binop(*op, lhs, rhs)
+        }
+    }
+}
+
+fn eval_binop(op: BinOp, lhs: Value, rhs: Value) -> Option<Value> {
+    use BinOp::*;
+    match (op, lhs, rhs) {
+        (And, Value::Bool(a), Value::Bool(b)) => Some(Value::Bool(a && b)),
+        (Or, Value::Bool(a), Value::Bool(b)) => Some(Value::Bool(a || b)),
+        (Add, Value::Num(a), Value::Num(b)) => Some(Value::Num(a + b)),
+        (Sub, Value::Num(a), Value::Num(b)) => Some(Value::Num(a - b)),
+        (Mul, Value::Num(a), Value::Num(b)) => Some(Value::Num(a * b)),
+        (Div, Value::Num(a), Value::Num(b)) => Some(Value::Num(a / b)),
+        (Eq, Value::Bool(a), Value::Bool(b)) => Some(Value::Bool(a == b)),
+        (Eq, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a == b)),
+        (Neq, Value::Bool(a), Value::Bool(b)) => Some(Value::Bool(a != b)),
+        (Neq, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a != b)),
+        (Lt, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a < b)),
+        (Le, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a <= b)),
+        (Gt, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a > b)),
+        (Ge, Value::Num(a), Value::Num(b)) => Some(Value::Bool(a >= b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_dsl {
+    use super::*;
+
+    fn vars(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn parses_and_evaluates_bool_expr() {
+        let expr = parse("A && B && !C").unwrap();
+        let vars = vars(&[
+            ("A", Value::Bool(true)),
+            ("B", Value::Bool(true)),
+            ("C", Value::Bool(false)),
+        ]);
+        assert_eq!(eval(&expr, &vars), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn parses_and_evaluates_arith_expr_with_precedence() {
+        let expr = parse("D + D * (E - F) / 25.5").unwrap();
+        let vars = vars(&[
+            ("D", Value::Num(33.3)),
+            ("E", Value::Num(10.)),
+            ("F", Value::Num(7.)),
+        ]);
+        assert_eq!(
+            eval(&expr, &vars),
+            Some(Value::Num(33.3 + 33.3 * 3. / 25.5))
+        );
+    }
+
+    #[test]
+    fn unknown_variable_returns_none() {
+        let expr = parse("A && Z").unwrap();
+        let vars = vars(&[("A", Value::Bool(true))]);
+        assert_eq!(eval(&expr, &vars), None);
+    }
+
+    #[test]
+    fn type_mismatch_returns_none() {
+        let expr = parse("A + 1").unwrap();
+        let vars = vars(&[("A", Value::Bool(true))]);
+        assert_eq!(eval(&expr, &vars), None);
+    }
+
+    #[test]
+    fn trailing_tokens_are_rejected() {
+        assert_eq!(parse("A && B C"), Err(DslError::TrailingTokens));
+    }
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::fmt;
+
+// Carries enough detail for a client to fix a bad request: which YAML
+// document position failed, and which field it was trying to populate.
+#[derive(Debug)]
+pub enum AssignmentError {
+    Yaml {
+        message: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        field: Option<String>,
+    },
+    UnknownSubstitution(String),
+    NoMatchingRule,
+}
+
+impl AssignmentError {
+    pub(crate) fn from_yaml(err: serde_path_to_error::Error<serde_yaml::Error>) -> Self {
+        let path = err.path().to_string();
+        let inner = err.into_inner();
+        let location = inner.location();
+
+        Self::Yaml {
+            message: inner.to_string(),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column()),
+            field: (!path.is_empty() && path != ".").then_some(path),
+        }
+    }
+
+    pub fn to_response(&self) -> ErrorResponse {
+        match self {
+            Self::Yaml {
+                message,
+                line,
+                column,
+                field,
+            } => ErrorResponse {
+                error: message.clone(),
+                line: *line,
+                column: *column,
+                field: field.clone(),
+            },
+            Self::UnknownSubstitution(name) => ErrorResponse {
+                error: format!("unknown substitution `{name}`"),
+                line: None,
+                column: None,
+                field: Some("substitution".to_string()),
+            },
+            Self::NoMatchingRule => ErrorResponse {
+                error: "no rule matched this input".to_string(),
+                line: None,
+                column: None,
+                field: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_response().error)
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+impl warp::reject::Reject for AssignmentError {}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub field: Option<String>,
+}
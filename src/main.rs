@@ -1,36 +1,212 @@
+mod dsl;
+mod error;
+mod rules;
 mod types;
 
+use error::AssignmentError;
 use serde::Deserialize;
-use types::{Input, Substitution};
-use warp::Filter;
+use std::convert::Infallible;
+use types::{Input, Output, Substitution};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
 
 #[derive(Debug, Deserialize)]
 struct AssignmentRequest {
     input: String,
     substitution: String,
+    // Shared YAML mapping merged into each document in `input` for fields it omits.
+    #[serde(default)]
+    defaults: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Json,
+    Yaml,
+    PlainText,
+}
+
+// Picks a response format from the `Accept` header. Media types are
+// case-insensitive per RFC 7231, so the header is lowercased before matching.
+fn negotiate_format(accept: Option<&str>) -> OutputFormat {
+    match accept.map(str::to_ascii_lowercase) {
+        Some(accept) if accept.contains("application/json") => OutputFormat::Json,
+        Some(accept) if accept.contains("application/yaml") => OutputFormat::Yaml,
+        _ => OutputFormat::PlainText,
+    }
+}
+
+// Renders an `Output` per the request's `Accept` header: JSON and YAML for
+// programmatic clients, falling back to the existing plain-text `Display` form.
+fn render_output(output: &Output, accept: Option<&str>) -> warp::reply::Response {
+    match negotiate_format(accept) {
+        OutputFormat::Json => warp::reply::json(output).into_response(),
+        OutputFormat::Yaml => {
+            let yaml = serde_yaml::to_string(output).expect("Output always serializes");
+            warp::reply::with_header(yaml, "content-type", "application/yaml").into_response()
+        }
+        OutputFormat::PlainText => output.to_string().into_response(),
+    }
+}
+
+async fn handle_assignment(
+    accept: Option<String>,
+    req: AssignmentRequest,
+) -> Result<impl Reply, Rejection> {
+    let subst = Substitution::from_str(&req.substitution).map_err(warp::reject::custom)?;
+    let input = Input::from_str_with_defaults(&req.input, req.defaults.as_deref())
+        .map_err(warp::reject::custom)?;
+    let output = subst.get_output(&input).map_err(warp::reject::custom)?;
+    Ok(render_output(&output, accept.as_deref()))
+}
+
+async fn handle_assignment_batch(req: AssignmentRequest) -> Result<impl Reply, Rejection> {
+    let subst = Substitution::from_str(&req.substitution).map_err(warp::reject::custom)?;
+    let inputs = Input::from_str_batch_with_defaults(&req.input, req.defaults.as_deref())
+        .map_err(warp::reject::custom)?;
+    let outputs = inputs
+        .iter()
+        .map(|input| subst.get_output(input).map(|output| output.to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(warp::reject::custom)?;
+    Ok(outputs.join("---\n"))
+}
+
+// Turns a rejected `AssignmentError` into a 400 with the YAML location/field that
+// caused it, instead of leaving the handler's rejection to warp's default 500.
+// Other rejections (a malformed JSON envelope, an unmapped route, a wrong HTTP
+// method) keep their own, more specific cause rather than being flattened into
+// a generic "not found".
+async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
+    if let Some(err) = err.find::<AssignmentError>() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&err.to_response()),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let (status, message) = if err.is_not_found() {
+        (StatusCode::NOT_FOUND, "not found".to_string())
+    } else if let Some(err) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, err.to_string())
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed".to_string(),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal server error".to_string(),
+        )
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&error::ErrorResponse {
+            error: message,
+            line: None,
+            column: None,
+            field: None,
+        }),
+        status,
+    ))
 }
 
 #[tokio::main]
 async fn main() {
-    let api = warp::post().and(warp::path("api"));
+    let api = warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("assignment"));
 
-    let assignment =
-        warp::path("assignment")
-            .and(warp::body::json())
-            .map(|req: AssignmentRequest| {
-                // TODO: handle errors
-                let subst = Substitution::from_str(&req.substitution).unwrap();
-                let input = Input::from_str(&req.input).unwrap();
-                subst.get_output(&input).unwrap().to_string()
-            });
+    let assignment = api
+        .and(warp::path::end())
+        .and(warp::header::optional::<String>("accept"))
+        .and(warp::body::json())
+        .and_then(handle_assignment);
+
+    // Batch mode: one request body, one YAML document per input, `---`-separated.
+    let assignment_batch = api
+        .and(warp::path("batch"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and_then(handle_assignment_batch);
 
-    let api_routes = api.and(assignment);
+    let api_routes = assignment.or(assignment_batch);
 
     let root = warp::get()
         .and(warp::path::end())
         .map(|| warp::reply::html(include_str!("index.html")));
 
-    let routes = root.or(api_routes);
+    let routes = root.or(api_routes).recover(recover);
 
     warp::serve(routes).run(([127, 0, 0, 1], 3030)).await
 }
+
+#[cfg(test)]
+mod test_main {
+    use super::*;
+
+    fn sample_output() -> Output {
+        let input = Input::from_str("A: true\nB: true\nC: false\nD: 33.3\nE: 10\nF: 7\n").unwrap();
+        Substitution::from_str("base")
+            .unwrap()
+            .get_output(&input)
+            .unwrap()
+    }
+
+    #[test]
+    fn negotiate_format_defaults_to_plain_text() {
+        assert_eq!(negotiate_format(None), OutputFormat::PlainText);
+    }
+
+    #[test]
+    fn negotiate_format_picks_json() {
+        assert_eq!(
+            negotiate_format(Some("application/json")),
+            OutputFormat::Json
+        );
+    }
+
+    #[test]
+    fn negotiate_format_picks_yaml() {
+        assert_eq!(
+            negotiate_format(Some("application/yaml")),
+            OutputFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn negotiate_format_falls_back_for_unrecognized_accept() {
+        assert_eq!(negotiate_format(Some("text/html")), OutputFormat::PlainText);
+    }
+
+    #[test]
+    fn negotiate_format_is_case_insensitive() {
+        assert_eq!(
+            negotiate_format(Some("APPLICATION/JSON")),
+            OutputFormat::Json
+        );
+        assert_eq!(
+            negotiate_format(Some("Application/Yaml")),
+            OutputFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn render_output_as_json_sets_json_content_type() {
+        let response = render_output(&sample_output(), Some("application/json"));
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn render_output_as_yaml_sets_yaml_content_type() {
+        let response = render_output(&sample_output(), Some("application/yaml"));
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/yaml"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::dsl::{self, Expr, Value};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const DEFAULT_RULES_YAML: &str = include_str!("rules.yaml");
+
+#[derive(Debug, Deserialize)]
+struct RawRule {
+    h: String,
+    when: String,
+    k: String,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    h: String,
+    when: Expr,
+    k: Expr,
+}
+
+pub type RuleSet = Vec<Rule>;
+
+// Rule config is embedded at compile time and parsed once on first use.
+static REGISTRY: OnceCell<HashMap<String, RuleSet>> = OnceCell::new();
+
+pub fn registry() -> &'static HashMap<String, RuleSet> {
+    REGISTRY.get_or_init(|| load_rules(DEFAULT_RULES_YAML))
+}
+
+fn load_rules(yaml: &str) -> HashMap<String, RuleSet> {
+    let raw: HashMap<String, Vec<RawRule>> =
+        serde_yaml::from_str(yaml).expect("embedded rules.yaml must be valid YAML");
+
+    raw.into_iter()
+        .map(|(name, rules)| {
+            let parsed = rules
+                .into_iter()
+                .map(|r| Rule {
+                    h: r.h,
+                    when: dsl::parse(&r.when)
+                        .unwrap_or_else(|e| panic!("invalid `when` in {name}: {e}")),
+                    k: dsl::parse(&r.k).unwrap_or_else(|e| panic!("invalid `k` in {name}: {e}")),
+                })
+                .collect();
+            (name, parsed)
+        })
+        .collect()
+}
+
+// Finds the first rule whose `when` is true and evaluates its `k`, mirroring
+// the "first match wins" behaviour of the rule list.
+pub fn evaluate(rules: &RuleSet, vars: &HashMap<String, Value>) -> Option<(String, f64)> {
+    let rule = rules
+        .iter()
+        .find(|rule| dsl::eval(&rule.when, vars) == Some(Value::Bool(true)))?;
+
+    match dsl::eval(&rule.k, vars)? {
+        Value::Num(n) => Some((rule.h.clone(), n)),
+        Value::Bool(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test_rules {
+    use super::*;
+
+    fn vars(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn default_registry_has_base_and_custom_substitutions() {
+        let registry = registry();
+        assert!(registry.contains_key("base"));
+        assert!(registry.contains_key("custom1"));
+        assert!(registry.contains_key("custom2"));
+    }
+
+    #[test]
+    fn evaluate_picks_first_matching_rule() {
+        let rules = &registry()["base"];
+        let vars = vars(&[
+            ("A", Value::Bool(true)),
+            ("B", Value::Bool(true)),
+            ("C", Value::Bool(false)),
+            ("D", Value::Num(33.3)),
+            ("E", Value::Num(10.)),
+            ("F", Value::Num(7.)),
+        ]);
+        assert_eq!(evaluate(rules, &vars), Some(("M".to_string(), 66.6)));
+    }
+
+    #[test]
+    fn evaluate_returns_none_when_no_rule_matches() {
+        let rules = &registry()["base"];
+        let vars = vars(&[
+            ("A", Value::Bool(true)),
+            ("B", Value::Bool(false)),
+            ("C", Value::Bool(true)),
+            ("D", Value::Num(33.3)),
+            ("E", Value::Num(10.)),
+            ("F", Value::Num(7.)),
+        ]);
+        assert_eq!(evaluate(rules, &vars), None);
+    }
+}
@@ -1,34 +1,139 @@
-use once_cell::sync::OnceCell;
-use serde::Deserialize;
+use crate::dsl::Value;
+use crate::error::AssignmentError;
+use crate::rules;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct Input {
+    // A/B/C pick the H bucket and have no sensible default: a request that
+    // omits one gets a validation error instead of a silently-guessed value.
     a: bool,
     b: bool,
     c: bool,
+    // D/E/F rarely change across a batch, so they fall back to documented
+    // defaults (the arithmetic identities) when a request omits them.
+    #[serde(default = "default_d")]
     d: f64,
+    #[serde(default)]
     e: i32,
+    #[serde(default)]
     f: i32,
 }
 
+fn default_d() -> f64 {
+    1.0
+}
+
 impl Input {
-    pub fn from_str(s: &str) -> Option<Self> {
+    pub fn from_str(s: &str) -> Result<Self, AssignmentError> {
         // Use YAML parser to save development time
-        serde_yaml::from_str(s).ok()
+        let deserializer = serde_yaml::Deserializer::from_str(s);
+        serde_path_to_error::deserialize(deserializer).map_err(AssignmentError::from_yaml)
+    }
+
+    // Parses a single request body containing several `---`-separated YAML
+    // documents into one `Input` per document.
+    pub fn from_str_batch(s: &str) -> Result<Vec<Self>, AssignmentError> {
+        serde_yaml::Deserializer::from_str(s)
+            .map(|doc| serde_path_to_error::deserialize(doc).map_err(AssignmentError::from_yaml))
+            .collect()
+    }
+
+    // Like `from_str`, but fields missing from `s` are first filled in from a
+    // shared `defaults` mapping, so a batch of near-identical documents only
+    // has to spell out the fields that actually differ.
+    //
+    // YAML anchors/aliases (`&x`/`*x`) already give this for free *within* a
+    // single document (see `anchors_and_aliases_work_within_one_document`
+    // below), but they cannot reach across the `---`-separated documents that
+    // `from_str_batch` splits on: per the YAML spec each document has its own
+    // anchor namespace, so a later document aliasing an earlier document's
+    // anchor fails with "unknown anchor" (see
+    // `anchors_do_not_carry_across_batch_documents`). `defaults` is the
+    // cross-document equivalent of an anchor block, applied by this crate
+    // instead of the YAML parser.
+    pub fn from_str_with_defaults(
+        s: &str,
+        defaults: Option<&str>,
+    ) -> Result<Self, AssignmentError> {
+        let Some(defaults) = defaults else {
+            return Self::from_str(s);
+        };
+
+        let defaults = parse_value(defaults)?;
+        let value = merge_with_defaults(parse_value(s)?, &defaults);
+        serde_path_to_error::deserialize(value).map_err(AssignmentError::from_yaml)
+    }
+
+    // Batch counterpart of `from_str_with_defaults`, applying the same
+    // defaults mapping to every `---`-separated document.
+    pub fn from_str_batch_with_defaults(
+        s: &str,
+        defaults: Option<&str>,
+    ) -> Result<Vec<Self>, AssignmentError> {
+        let Some(defaults) = defaults else {
+            return Self::from_str_batch(s);
+        };
+
+        let defaults = parse_value(defaults)?;
+        serde_yaml::Deserializer::from_str(s)
+            .map(|doc| {
+                let value =
+                    serde_path_to_error::deserialize(doc).map_err(AssignmentError::from_yaml)?;
+                let value = merge_with_defaults(value, &defaults);
+                serde_path_to_error::deserialize(value).map_err(AssignmentError::from_yaml)
+            })
+            .collect()
+    }
+
+    // Builds the variable map the rule DSL evaluates `when`/`k` expressions against
+    fn vars(&self) -> HashMap<String, Value> {
+        HashMap::from([
+            ("A".to_string(), Value::Bool(self.a)),
+            ("B".to_string(), Value::Bool(self.b)),
+            ("C".to_string(), Value::Bool(self.c)),
+            ("D".to_string(), Value::Num(self.d)),
+            ("E".to_string(), Value::Num(self.e as f64)),
+            ("F".to_string(), Value::Num(self.f as f64)),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum HValue {
-    M,
-    P,
-    T,
+fn parse_value(s: &str) -> Result<serde_yaml::Value, AssignmentError> {
+    let deserializer = serde_yaml::Deserializer::from_str(s);
+    serde_path_to_error::deserialize(deserializer).map_err(AssignmentError::from_yaml)
 }
 
-#[derive(Debug, PartialEq)]
+// Fills in any mapping key present in `defaults` but missing from `value`.
+fn merge_with_defaults(
+    mut value: serde_yaml::Value,
+    defaults: &serde_yaml::Value,
+) -> serde_yaml::Value {
+    if let (serde_yaml::Value::Mapping(map), serde_yaml::Value::Mapping(default_map)) =
+        (&mut value, defaults)
+    {
+        for (key, default_value) in default_map {
+            map.entry(key.clone())
+                .or_insert_with(|| default_value.clone());
+        }
+    }
+
+    value
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct HValue(String);
+
+impl fmt::Display for HValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Output {
     h: HValue,
     k: f64,
@@ -37,90 +142,34 @@ pub struct Output {
 // Implement Display trait and use .to_string() for serialization
 impl fmt::Display for Output {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "H: {:?}\nK: {}\n", self.h, self.k)
+        write!(f, "H: {}\nK: {}\n", self.h, self.k)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Substitution {
-    Base,
-    Custom1,
-    Custom2,
-}
-
-// Use OnceCell to store helper map for H calculations
-#[allow(clippy::type_complexity)]
-static HVALMAP: OnceCell<HashMap<Substitution, HashMap<HValue, (bool, bool, bool)>>> =
-    OnceCell::new();
+// A substitution is just a name into the rule registry loaded from `rules.yaml`,
+// so adding a new one is an edit to that file rather than to Rust code. The file
+// is embedded at build time, so such an edit takes effect only after a rebuild.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution(String);
 
 impl Substitution {
     // helper method to transform string to Substitution variant
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "base" => Some(Self::Base),
-            "custom1" => Some(Self::Custom1),
-            "custom2" => Some(Self::Custom2),
-            _ => None,
+    pub fn from_str(s: &str) -> Result<Self, AssignmentError> {
+        if rules::registry().contains_key(s) {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(AssignmentError::UnknownSubstitution(s.to_string()))
         }
     }
 
     // This method transforms Input into Output according to current substitution variant
-    pub fn get_output(&self, input: &Input) -> Option<Output> {
-        let h = self.get_h_value(&input)?;
-        let k = self.get_k_value(&input, h);
-
-        Some(Output { h, k })
-    }
-
-    // Method for H calculations
-    // To make it simplier H will be calculated by comparing tuples of (A, B, C)
-    // I.e A && B && !C is equal to (A, B, C) == (true, true, false)
-    fn get_h_value(&self, input: &Input) -> Option<HValue> {
-        let map = HVALMAP.get_or_init(|| {
-            let mut map = HashMap::new();
-            let mut base_map = HashMap::new();
-            base_map.insert(HValue::M, (true, true, false));
-            base_map.insert(HValue::P, (true, true, true));
-            base_map.insert(HValue::T, (false, true, true));
-
-            // Override expressions for Custom2
-            let mut custom2_map = base_map.clone();
-            custom2_map.insert(HValue::T, (true, true, false));
-            custom2_map.insert(HValue::M, (true, false, true));
-            map.insert(Self::Custom2, custom2_map);
-
-            map.insert(Self::Base, base_map);
-            map
-        });
-
-        // Use Base if there is no overrides for current Substitution
-        let subst_map = map.get(self).or_else(|| map.get(&Self::Base))?;
-
-        for (h, expectation) in subst_map {
-            if *expectation == (input.a, input.b, input.c) {
-                return Some(*h);
-            }
-        }
-
-        None
-    }
-
-    // Method for K calculations
-    fn get_k_value(&self, input: &Input, value_h: HValue) -> f64 {
-        let d = input.d;
-        let e = input.e as f64;
-        let f = input.f as f64;
-
-        match (self, value_h) {
-            // override expressions for Custom2
-            (Self::Custom2, HValue::M) => f + d + d * e / 100.,
-            // override expressions for Custom1
-            (Self::Custom1, HValue::P) => 2. * d + d * e / 100.,
-            // base expressions. It uses _ to match any Substitution
-            (_, HValue::M) => d + d * e / 10.,
-            (_, HValue::P) => d + d * (e - f) / 25.5,
-            (_, HValue::T) => d - d * f / 30.,
-        }
+    pub fn get_output(&self, input: &Input) -> Result<Output, AssignmentError> {
+        let rules = rules::registry()
+            .get(&self.0)
+            .expect("validated against the registry in from_str");
+        let (h, k) =
+            rules::evaluate(rules, &input.vars()).ok_or(AssignmentError::NoMatchingRule)?;
+        Ok(Output { h: HValue(h), k })
     }
 }
 
@@ -147,22 +196,205 @@ F: 7
             f: 7,
         };
 
-        let input = Input::from_str(input_str);
-        assert_eq!(input, Some(expected_input));
+        let input = Input::from_str(input_str).unwrap();
+        assert_eq!(input, expected_input);
+    }
+
+    #[test]
+    fn input_falls_back_to_defaults_for_missing_def_fields() {
+        let input_str = "A: true\nB: true\nC: false\n";
+
+        let input = Input::from_str(input_str).unwrap();
+        assert_eq!(
+            input,
+            Input {
+                a: true,
+                b: true,
+                c: false,
+                d: 1.0,
+                e: 0,
+                f: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn input_from_str_requires_abc() {
+        let input_str = "D: 1.0\n";
+
+        assert!(Input::from_str(input_str).is_err());
+    }
+
+    #[test]
+    fn input_with_defaults_fills_in_missing_fields_from_shared_block() {
+        let defaults = "D: 33.3\nE: 10\nF: 7\n";
+        let input_str = "A: true\nB: true\nC: false\n";
+
+        let input = Input::from_str_with_defaults(input_str, Some(defaults)).unwrap();
+        assert_eq!(
+            input,
+            Input {
+                a: true,
+                b: true,
+                c: false,
+                d: 33.3,
+                e: 10,
+                f: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn input_with_defaults_lets_a_document_override_a_shared_field() {
+        let defaults = "D: 33.3\nE: 10\nF: 7\n";
+        let input_str = "A: true\nB: true\nC: false\nF: 0\n";
+
+        let input = Input::from_str_with_defaults(input_str, Some(defaults)).unwrap();
+        assert_eq!(
+            input,
+            Input {
+                a: true,
+                b: true,
+                c: false,
+                d: 33.3,
+                e: 10,
+                f: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn input_batch_with_defaults_applies_to_every_document() {
+        let defaults = "D: 33.3\nE: 10\nF: 7\n";
+        let batch_str = "A: true\nB: true\nC: false\n---\nA: false\nB: true\nC: true\n";
+
+        let inputs = Input::from_str_batch_with_defaults(batch_str, Some(defaults)).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                Input {
+                    a: true,
+                    b: true,
+                    c: false,
+                    d: 33.3,
+                    e: 10,
+                    f: 7,
+                },
+                Input {
+                    a: false,
+                    b: true,
+                    c: true,
+                    d: 33.3,
+                    e: 10,
+                    f: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn anchors_and_aliases_work_within_one_document() {
+        let input_str = "A: &flag true\nB: *flag\nC: false\n";
+
+        let input = Input::from_str(input_str).unwrap();
+        assert_eq!(
+            input,
+            Input {
+                a: true,
+                b: true,
+                c: false,
+                d: 1.0,
+                e: 0,
+                f: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn anchors_do_not_carry_across_batch_documents() {
+        let batch_str = "A: &flag true\nB: true\nC: false\n---\nA: *flag\nB: true\nC: true\n";
+
+        let err = Input::from_str_batch(batch_str).unwrap_err();
+        assert!(matches!(err, AssignmentError::Yaml { .. }));
+    }
+
+    #[test]
+    fn input_batch_can_be_parsed() {
+        let batch_str = r"
+A: true
+B: true
+C: false
+D: 33.3
+E: 10
+F: 7
+---
+A: false
+B: true
+C: true
+D: 1.0
+E: 2
+F: 3
+";
+        let expected = vec![
+            Input {
+                a: true,
+                b: true,
+                c: false,
+                d: 33.3,
+                e: 10,
+                f: 7,
+            },
+            Input {
+                a: false,
+                b: true,
+                c: true,
+                d: 1.0,
+                e: 2,
+                f: 3,
+            },
+        ];
+
+        assert_eq!(Input::from_str_batch(batch_str).unwrap(), expected);
+    }
+
+    #[test]
+    fn input_batch_returns_err_if_any_document_is_invalid() {
+        let batch_str = "A: true\n---\nBAD STRING";
+
+        assert!(Input::from_str_batch(batch_str).is_err());
     }
 
     #[test]
-    fn input_from_str_returns_none() {
+    fn input_from_str_returns_err_for_bad_yaml() {
         let input_str = "BAD STRING";
 
-        let input = Input::from_str(input_str);
-        assert_eq!(input, None);
+        assert!(Input::from_str(input_str).is_err());
+    }
+
+    #[test]
+    fn input_from_str_reports_line_and_field_for_bad_value() {
+        let input_str = r"
+A: true
+B: true
+C: false
+D: 33.3
+E: abc
+F: 7
+";
+        let err = Input::from_str(input_str).unwrap_err();
+        match err {
+            AssignmentError::Yaml { line, field, .. } => {
+                assert_eq!(line, Some(6));
+                assert_eq!(field, Some("E".to_string()));
+            }
+            other => panic!("expected a Yaml error, got {other:?}"),
+        }
     }
 
     #[test]
     fn output_can_be_serialized() {
         let output = Output {
-            h: HValue::M,
+            h: HValue("M".to_string()),
             k: 33.33,
         };
 
@@ -170,73 +402,61 @@ F: 7
     }
 
     #[test]
-    fn substitution_get_output_works() {
-        let input = Input {
-            a: true,
-            b: true,
-            c: false,
-            d: 33.3,
-            e: 10,
-            f: 7,
-        };
-        let expexted_output = Output {
-            h: HValue::M,
-            k: 66.6,
+    fn output_can_be_serialized_as_yaml() {
+        let output = Output {
+            h: HValue("M".to_string()),
+            k: 33.33,
         };
 
-        let subst = Substitution::Base;
-        let output_opt = subst.get_output(&input);
-        assert_eq!(output_opt, Some(expexted_output));
+        assert_eq!(serde_yaml::to_string(&output).unwrap(), "h: M\nk: 33.33\n");
     }
 
     #[test]
-    fn substitution_get_output_returns_none() {
-        let input = Input {
-            a: true,
-            b: false,
-            c: true,
-            d: 33.3,
-            e: 10,
-            f: 7,
-        };
-
-        let subst = Substitution::Base;
-        let output_opt = subst.get_output(&input);
-        assert_eq!(output_opt, None);
+    fn substitution_from_str_returns_err_for_unknown_name() {
+        let err = Substitution::from_str("unknown").unwrap_err();
+        assert!(matches!(err, AssignmentError::UnknownSubstitution(name) if name == "unknown"));
     }
 
     #[test]
-    fn get_h_value_works() {
+    fn substitution_get_output_works() {
         let input = Input {
             a: true,
             b: true,
             c: false,
-            d: 30.,
+            d: 33.3,
             e: 10,
             f: 7,
         };
+        let expexted_output = Output {
+            h: HValue("M".to_string()),
+            k: 66.6,
+        };
 
-        let h_opt = Substitution::Base.get_h_value(&input);
-        assert_eq!(h_opt, Some(HValue::M));
+        let subst = Substitution::from_str("base").unwrap();
+        let output = subst.get_output(&input).unwrap();
+        assert_eq!(output, expexted_output);
     }
 
     #[test]
-    fn get_h_value_returns_none() {
+    fn substitution_get_output_returns_err_when_no_rule_matches() {
         let input = Input {
             a: true,
             b: false,
             c: true,
-            d: 30.,
+            d: 33.3,
             e: 10,
             f: 7,
         };
 
-        let h_opt = Substitution::Base.get_h_value(&input);
-        assert_eq!(h_opt, None);
+        let subst = Substitution::from_str("base").unwrap();
+        assert!(matches!(
+            subst.get_output(&input),
+            Err(AssignmentError::NoMatchingRule)
+        ));
     }
 
     #[test]
-    fn get_h_value_uses_overrides() {
+    fn substitution_get_output_uses_overrides() {
         let input = Input {
             a: true,
             b: true,
@@ -246,41 +466,49 @@ F: 7
             f: 7,
         };
 
-        let h_for_base = Substitution::Base.get_h_value(&input);
-        let h_for_custom = Substitution::Custom2.get_h_value(&input);
-        assert_eq!(h_for_base, Some(HValue::M));
-        assert_eq!(h_for_custom, Some(HValue::T));
-    }
-
-    #[test]
-    fn get_k_value_works() {
-        let input = Input {
-            a: true,
-            b: true,
-            c: false,
-            d: 30.,
-            e: 10,
-            f: 7,
-        };
+        let base = Substitution::from_str("base").unwrap().get_output(&input);
+        let custom2 = Substitution::from_str("custom2")
+            .unwrap()
+            .get_output(&input);
 
-        let k = Substitution::Base.get_k_value(&input, HValue::M);
-        assert_eq!(k, 60.);
+        assert_eq!(
+            base.unwrap(),
+            Output {
+                h: HValue("M".to_string()),
+                k: 60.
+            }
+        );
+        assert_eq!(
+            custom2.unwrap(),
+            Output {
+                h: HValue("T".to_string()),
+                k: 30. - 30. * 7. / 30.
+            }
+        );
     }
 
     #[test]
-    fn get_k_value_uses_overrides() {
+    fn custom2_overrides_k_for_its_own_h_and_condition() {
         let input = Input {
             a: true,
-            b: true,
-            c: false,
+            b: false,
+            c: true,
             d: 30.,
             e: 10,
             f: 7,
         };
 
-        let k_for_base = Substitution::Base.get_k_value(&input, HValue::M);
-        let k_for_custom = Substitution::Custom2.get_k_value(&input, HValue::M);
-        assert_eq!(k_for_base, 60.);
-        assert_eq!(k_for_custom, 40.);
+        let output = Substitution::from_str("custom2")
+            .unwrap()
+            .get_output(&input)
+            .unwrap();
+
+        assert_eq!(
+            output,
+            Output {
+                h: HValue("M".to_string()),
+                k: 7. + 30. + 30. * 10. / 100.
+            }
+        );
     }
 }